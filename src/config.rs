@@ -0,0 +1,270 @@
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use serde::Deserialize;
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+const CONFIG_FILE_NAMES: &[&str] = &["fillout.toml", "fillout.yaml", "fillout.yml"];
+
+const DEFAULT_DELIMITER_OPEN: &str = "{{";
+const DEFAULT_DELIMITER_CLOSE: &str = "}}";
+const DEFAULT_CSV_DELIMITER: u8 = b',';
+const DEFAULT_TEMPLATE_EXTENSION: &str = "tmpl";
+
+/// Settings shared by all templates and data files in a run, loaded from an optional
+/// `fillout.toml`/`fillout.yaml` project file.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Marks the start of a placeholder, e.g. `{{`.
+    pub delimiter_open: String,
+    /// Marks the end of a placeholder, e.g. `}}`.
+    pub delimiter_close: String,
+    /// Default directory for output-files, used when `--output-dir` is not given.
+    pub output_dir: Option<PathBuf>,
+    /// Directories searched for templates referenced by name (e.g. includes).
+    pub template_dirs: Vec<PathBuf>,
+    /// Field delimiter used when parsing the data file.
+    pub csv_delimiter: u8,
+    /// File extension (without the leading dot) a template-file must have, matched
+    /// case-insensitively. Also used to recognize template-files under a directory or glob
+    /// template-file argument.
+    pub template_extension: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            delimiter_open: DEFAULT_DELIMITER_OPEN.to_string(),
+            delimiter_close: DEFAULT_DELIMITER_CLOSE.to_string(),
+            output_dir: None,
+            template_dirs: vec![],
+            csv_delimiter: DEFAULT_CSV_DELIMITER,
+            template_extension: DEFAULT_TEMPLATE_EXTENSION.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct RawConfig {
+    delimiter_open: Option<String>,
+    delimiter_close: Option<String>,
+    output_dir: Option<PathBuf>,
+    template_dirs: Option<Vec<PathBuf>>,
+    csv_delimiter: Option<char>,
+    template_extension: Option<String>,
+}
+
+impl Config {
+    /// Loads the project config by walking up from the current directory looking for
+    /// `fillout.toml` or `fillout.yaml`/`fillout.yml`, falling back to built-in defaults
+    /// (`{{`/`}}` delimiters, comma-delimited data file) when none is found.
+    pub fn load() -> Result<Config> {
+        let cwd = env::current_dir().context("Failed to determine current directory")?;
+        match find_config_file(&cwd) {
+            Some(path) => Config::load_from(&path),
+            None => Ok(Config::default()),
+        }
+    }
+
+    fn load_from(path: &Path) -> Result<Config> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {:?}", path))?;
+        let raw: RawConfig = if path.extension().and_then(|ext| ext.to_str()) == Some("toml") {
+            toml::from_str(&text)
+                .with_context(|| format!("Failed to parse config file {:?}", path))?
+        } else {
+            serde_yaml::from_str(&text)
+                .with_context(|| format!("Failed to parse config file {:?}", path))?
+        };
+
+        // Paths in the config file are relative to the config file itself, not the cwd.
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut config = Config::default();
+        if let Some(delimiter_open) = raw.delimiter_open {
+            config.delimiter_open = delimiter_open;
+        }
+        if let Some(delimiter_close) = raw.delimiter_close {
+            config.delimiter_close = delimiter_close;
+        }
+        config.output_dir = raw.output_dir.map(|dir| base_dir.join(dir));
+        config.template_dirs = raw
+            .template_dirs
+            .unwrap_or_default()
+            .into_iter()
+            .map(|dir| base_dir.join(dir))
+            .collect();
+        if let Some(csv_delimiter) = raw.csv_delimiter {
+            if !csv_delimiter.is_ascii() {
+                bail!(
+                    "Invalid csv-delimiter {:?} in config file {:?}: must be an ASCII character",
+                    csv_delimiter,
+                    path
+                );
+            }
+            config.csv_delimiter = csv_delimiter as u8;
+        }
+        if let Some(template_extension) = raw.template_extension {
+            config.template_extension = template_extension;
+        }
+
+        if config.delimiter_open.is_empty() || config.delimiter_close.is_empty() {
+            bail!(
+                "Invalid delimiters in config file {:?}: delimiter-open and delimiter-close must not be empty",
+                path
+            );
+        }
+        if config.delimiter_open == config.delimiter_close {
+            bail!(
+                "Invalid delimiters in config file {:?}: delimiter-open and delimiter-close must differ",
+                path
+            );
+        }
+
+        Ok(config)
+    }
+}
+
+fn find_config_file(start: &Path) -> Option<PathBuf> {
+    let mut dir = Some(start);
+    while let Some(d) = dir {
+        for name in CONFIG_FILE_NAMES {
+            let candidate = d.join(name);
+            if candidate.is_file() {
+                return Some(candidate);
+            }
+        }
+        dir = d.parent();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::atomic::Ordering;
+
+    /// A directory under the OS temp dir that is removed again when dropped, so tests don't
+    /// need an external crate to get an isolated scratch directory.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = env::temp_dir().join(format!(
+                "fillout-config-test-{}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn write_config(dir: &Path, name: &str, contents: &str) -> PathBuf {
+        let path = dir.join(name);
+        File::create(&path)
+            .unwrap()
+            .write_all(contents.as_bytes())
+            .unwrap();
+        path
+    }
+
+    #[test]
+    fn load_from_defaults_when_absent() {
+        let dir = TempDir::new();
+        let path = write_config(dir.path(), "fillout.toml", "");
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.delimiter_open, "{{");
+        assert_eq!(config.delimiter_close, "}}");
+        assert_eq!(config.csv_delimiter, b',');
+        assert_eq!(config.template_extension, "tmpl");
+    }
+
+    #[test]
+    fn load_from_merges_overrides() {
+        let dir = TempDir::new();
+        let path = write_config(
+            dir.path(),
+            "fillout.toml",
+            "delimiter-open = \"<%\"\ndelimiter-close = \"%>\"\ncsv-delimiter = \";\"\n",
+        );
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.delimiter_open, "<%");
+        assert_eq!(config.delimiter_close, "%>");
+        assert_eq!(config.csv_delimiter, b';');
+    }
+
+    #[test]
+    fn load_from_resolves_paths_relative_to_config_file() {
+        let dir = TempDir::new();
+        let path = write_config(
+            dir.path(),
+            "fillout.toml",
+            "output-dir = \"out\"\ntemplate-dirs = [\"partials\"]\n",
+        );
+        let config = Config::load_from(&path).unwrap();
+        assert_eq!(config.output_dir, Some(dir.path().join("out")));
+        assert_eq!(config.template_dirs, vec![dir.path().join("partials")]);
+    }
+
+    #[test]
+    fn load_from_rejects_empty_delimiter() {
+        let dir = TempDir::new();
+        let path = write_config(dir.path(), "fillout.toml", "delimiter-open = \"\"\n");
+        assert!(Config::load_from(&path).is_err());
+    }
+
+    #[test]
+    fn load_from_rejects_equal_delimiters() {
+        let dir = TempDir::new();
+        let path = write_config(
+            dir.path(),
+            "fillout.toml",
+            "delimiter-open = \"||\"\ndelimiter-close = \"||\"\n",
+        );
+        assert!(Config::load_from(&path).is_err());
+    }
+
+    #[test]
+    fn load_from_rejects_non_ascii_csv_delimiter() {
+        let dir = TempDir::new();
+        let path = write_config(dir.path(), "fillout.toml", "csv-delimiter = \"é\"\n");
+        assert!(Config::load_from(&path).is_err());
+    }
+
+    #[test]
+    fn find_config_file_walks_up_from_subdirectory() {
+        let dir = TempDir::new();
+        write_config(dir.path(), "fillout.toml", "");
+        let subdir = dir.path().join("a/b");
+        std::fs::create_dir_all(&subdir).unwrap();
+        assert_eq!(
+            find_config_file(&subdir),
+            Some(dir.path().join("fillout.toml"))
+        );
+    }
+
+    #[test]
+    fn find_config_file_none_when_absent() {
+        let dir = TempDir::new();
+        assert_eq!(find_config_file(dir.path()), None);
+    }
+}
@@ -2,12 +2,13 @@ use anyhow::bail;
 use csv::Trim;
 use std::collections::HashMap;
 
-pub fn parse(input: &[u8]) -> anyhow::Result<HashMap<String, String>> {
+pub fn parse(input: &[u8], delimiter: u8) -> anyhow::Result<HashMap<String, String>> {
     let mut rdr = csv::ReaderBuilder::new()
         .has_headers(false)
         .flexible(true) // we want the same error emssage on the first record as the rest
         .trim(Trim::All)
         .comment(Some(b'#'))
+        .delimiter(delimiter)
         .from_reader(input);
 
     let mut output = HashMap::new();
@@ -0,0 +1,54 @@
+use crate::template::Error;
+use std::path::Path;
+
+/// Finds the 1-based line/column and the full text of the line containing byte offset `pos`
+/// in `corpus`.
+fn locate(corpus: &str, pos: usize) -> (usize, usize, &str) {
+    let pos = pos.min(corpus.len());
+    let line_start = corpus[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    let line_end = corpus[pos..]
+        .find('\n')
+        .map(|i| pos + i)
+        .unwrap_or_else(|| corpus.len());
+    let line = corpus[..pos].matches('\n').count() + 1;
+    let column = corpus[line_start..pos].chars().count() + 1;
+    (line, column, &corpus[line_start..line_end])
+}
+
+/// Prints every error in `errors` against `corpus`, with the offending source line and a caret
+/// pointing at the byte offset each error occurred at, so a template with several malformed
+/// placeholders can be fixed in one pass instead of one recompile per error.
+pub fn report(path: &Path, corpus: &str, errors: &[Error]) {
+    for error in errors {
+        let (line, column, line_text) = locate(corpus, error.offset());
+        eprintln!(
+            "error: {} ({}:{}:{})",
+            error.short_message(),
+            path.display(),
+            line,
+            column
+        );
+        eprintln!("  {}", line_text);
+        eprintln!("  {}^", " ".repeat(column - 1));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_first_line() {
+        assert_eq!(locate("lorem\nipsum", 2), (1, 3, "lorem"));
+    }
+
+    #[test]
+    fn locate_second_line() {
+        assert_eq!(locate("lorem\nipsum", 8), (2, 3, "ipsum"));
+    }
+
+    #[test]
+    fn locate_clamps_to_corpus_end() {
+        assert_eq!(locate("lorem", 100), (1, 6, "lorem"));
+    }
+}
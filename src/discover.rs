@@ -0,0 +1,167 @@
+use anyhow::bail;
+use anyhow::Context;
+use anyhow::Result;
+use glob::glob_with;
+use glob::MatchOptions;
+use std::path::Path;
+use std::path::PathBuf;
+
+const GLOB_METACHARS: &[char] = &['*', '?', '[', ']'];
+
+/// A discovered template-file, paired with the sub-path it should be placed at relative to
+/// an explicit `--output-dir`, preserving the directory structure it was found under.
+pub struct Input {
+    pub template_file: PathBuf,
+    pub relative_path: PathBuf,
+}
+
+/// Expands `args` into the template-files they refer to.
+///
+/// Each argument is either a plain file (which must have the `extension` file extension,
+/// matched case-insensitively), a directory (recursively walked for files with `extension`),
+/// or a glob pattern such as `templates/**/*.tmpl`. A file whose name starts with `_` is
+/// treated as an include-only partial and is skipped by directory and glob expansion, so it
+/// doesn't also produce an output-file of its own; it can still be loaded by an include.
+pub fn expand(args: &[PathBuf], extension: &str) -> Result<Vec<Input>> {
+    let mut inputs = vec![];
+    for arg in args {
+        if arg.is_dir() {
+            let pattern = arg.join(format!("**/*.{}", extension));
+            expand_pattern(&pattern, arg, &mut inputs)?;
+        } else if is_glob_pattern(arg) {
+            let base = glob_base(arg);
+            expand_pattern(arg, &base, &mut inputs)?;
+        } else {
+            if !has_extension(arg, extension) {
+                bail!(
+                    "template-file must have .{} file extension: {:?}",
+                    extension,
+                    arg
+                );
+            }
+            let template_file = arg
+                .canonicalize()
+                .with_context(|| format!("Failed to resolve template-file: {:?}", arg))?;
+            let relative_path = PathBuf::from(template_file.file_name().unwrap());
+            inputs.push(Input {
+                template_file,
+                relative_path,
+            });
+        }
+    }
+    Ok(inputs)
+}
+
+fn expand_pattern(pattern: &Path, base: &Path, inputs: &mut Vec<Input>) -> Result<()> {
+    let options = MatchOptions {
+        case_sensitive: false,
+        require_literal_separator: true,
+        require_literal_leading_dot: false,
+    };
+    let pattern_str = pattern
+        .to_str()
+        .with_context(|| format!("Template-file pattern is not valid UTF-8: {:?}", pattern))?;
+    let base = base
+        .canonicalize()
+        .with_context(|| format!("Failed to resolve template-dir: {:?}", base))?;
+    for entry in glob_with(pattern_str, options)
+        .with_context(|| format!("Invalid template-file pattern: {:?}", pattern_str))?
+    {
+        let template_file =
+            entry.with_context(|| format!("Failed to read glob entry for {:?}", pattern_str))?;
+        let template_file = template_file
+            .canonicalize()
+            .with_context(|| format!("Failed to resolve template-file: {:?}", template_file))?;
+        if is_partial(&template_file) {
+            continue;
+        }
+        let relative_path = template_file
+            .strip_prefix(&base)
+            .unwrap_or(&template_file)
+            .to_path_buf();
+        inputs.push(Input {
+            template_file,
+            relative_path,
+        });
+    }
+    Ok(())
+}
+
+fn is_glob_pattern(path: &Path) -> bool {
+    path.to_string_lossy()
+        .chars()
+        .any(|c| GLOB_METACHARS.contains(&c))
+}
+
+/// The literal (non-wildcard) leading directory components of a glob pattern, used as the
+/// base a matched template-file's relative sub-path is computed against, or `.` when the
+/// pattern has none.
+fn glob_base(pattern: &Path) -> PathBuf {
+    let mut base = PathBuf::new();
+    for component in pattern.components() {
+        if is_glob_pattern(Path::new(component.as_os_str())) {
+            break;
+        }
+        base.push(component);
+    }
+    if base.as_os_str().is_empty() {
+        base.push(".");
+    }
+    base
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| ext.eq_ignore_ascii_case(extension))
+        .unwrap_or(false)
+}
+
+/// Whether `path` names an include-only partial, by convention a file whose name starts
+/// with `_`, e.g. `_signature.tmpl`.
+fn is_partial(path: &Path) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .map(|stem| stem.starts_with('_'))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_glob_pattern_detects_metachars() {
+        assert!(is_glob_pattern(Path::new("templates/**/*.tmpl")));
+        assert!(is_glob_pattern(Path::new("templates/a?.tmpl")));
+        assert!(is_glob_pattern(Path::new("templates/[ab].tmpl")));
+        assert!(!is_glob_pattern(Path::new("templates/a.tmpl")));
+    }
+
+    #[test]
+    fn glob_base_stops_at_first_wildcard_component() {
+        assert_eq!(
+            glob_base(Path::new("templates/sub/**/*.tmpl")),
+            Path::new("templates/sub")
+        );
+        assert_eq!(
+            glob_base(Path::new("templates/*.tmpl")),
+            Path::new("templates")
+        );
+    }
+
+    #[test]
+    fn has_extension_matches_case_insensitively() {
+        assert!(has_extension(Path::new("a.tmpl"), "tmpl"));
+        assert!(has_extension(Path::new("a.TMPL"), "tmpl"));
+        assert!(!has_extension(Path::new("a.txt"), "tmpl"));
+        assert!(!has_extension(Path::new("a"), "tmpl"));
+    }
+
+    #[test]
+    fn is_partial_detects_leading_underscore() {
+        assert!(is_partial(Path::new("_signature.tmpl")));
+        assert!(is_partial(Path::new("templates/_signature.tmpl")));
+        assert!(!is_partial(Path::new("signature.tmpl")));
+    }
+}
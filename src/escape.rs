@@ -0,0 +1,120 @@
+/// How a substituted placeholder value is escaped before being written to the output.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Escape {
+    /// The value is written verbatim.
+    None,
+    /// HTML-entity escaping of `&`, `<`, `>`, `"` and `'`.
+    Html,
+    /// POSIX shell single-quoting.
+    Shell,
+    /// RFC 4180 CSV-quoting.
+    Csv,
+}
+
+impl Escape {
+    /// The default escaping mode for an output-file with the given extension, falling back
+    /// to `Escape::None` for an unrecognized or missing extension.
+    pub fn for_extension(extension: Option<&str>) -> Escape {
+        match extension.map(|ext| ext.to_ascii_lowercase()).as_deref() {
+            Some("html") | Some("htm") => Escape::Html,
+            Some("sh") | Some("bash") => Escape::Shell,
+            Some("csv") => Escape::Csv,
+            _ => Escape::None,
+        }
+    }
+
+    /// Parses the name following a `|` in a placeholder, e.g. `{{ name | raw }}`.
+    pub fn from_name(name: &str) -> Option<Escape> {
+        match name {
+            "raw" | "none" => Some(Escape::None),
+            "html" => Some(Escape::Html),
+            "shell" | "sh" => Some(Escape::Shell),
+            "csv" => Some(Escape::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, value: &str) -> String {
+        match self {
+            Escape::None => value.to_string(),
+            Escape::Html => escape_html(value),
+            Escape::Shell => escape_shell(value),
+            Escape::Csv => escape_csv(value),
+        }
+    }
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_shell(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+fn escape_csv(value: &str) -> String {
+    if value.contains(['"', ',', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn for_extension() {
+        assert_eq!(Escape::for_extension(Some("html")), Escape::Html);
+        assert_eq!(Escape::for_extension(Some("HTM")), Escape::Html);
+        assert_eq!(Escape::for_extension(Some("sh")), Escape::Shell);
+        assert_eq!(Escape::for_extension(Some("csv")), Escape::Csv);
+        assert_eq!(Escape::for_extension(Some("txt")), Escape::None);
+        assert_eq!(Escape::for_extension(None), Escape::None);
+    }
+
+    #[test]
+    fn from_name() {
+        assert_eq!(Escape::from_name("raw"), Some(Escape::None));
+        assert_eq!(Escape::from_name("html"), Some(Escape::Html));
+        assert_eq!(Escape::from_name("bogus"), None);
+    }
+
+    #[test]
+    fn html() {
+        assert_eq!(
+            Escape::Html.apply("<a href=\"x\">&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn shell() {
+        assert_eq!(Escape::Shell.apply("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn csv_plain() {
+        assert_eq!(Escape::Csv.apply("lorem"), "lorem");
+    }
+
+    #[test]
+    fn csv_quoted() {
+        assert_eq!(
+            Escape::Csv.apply("lo,rem \"ipsum\""),
+            "\"lo,rem \"\"ipsum\"\"\""
+        );
+    }
+}
@@ -1,10 +1,17 @@
+mod config;
 mod data;
+mod diagnostics;
+mod discover;
+mod escape;
 mod template;
 
 use anyhow::anyhow;
+use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
-use std::ffi::OsStr;
+use escape::Escape;
+use std::collections::HashMap;
+use std::path::Path;
 use std::path::PathBuf;
 use structopt::StructOpt;
 use terminal_size::terminal_size;
@@ -23,6 +30,15 @@ use terminal_size::Width;
 /// A single placeholder name may optionally recur multiple times in multiple in multiple template-files.
 /// If so, they are replaced with the same value each time.
 ///
+/// A placeholder whose name starts with `>`, e.g. {{> header }}, includes another template by
+/// name instead of substituting a value. The named template is looked up in the configured
+/// template-dirs and is itself scanned for placeholders and further includes. Include cycles are
+/// rejected with the chain of names that caused them.
+///
+/// A placeholder's value is escaped before being substituted, in a mode chosen by the output
+/// file's extension (html/htm, sh/bash, csv - otherwise no escaping). A placeholder can override
+/// this with a pipe suffix, e.g. {{ name | raw }}, naming html, shell, csv or raw/none.
+///
 /// The data file is a headerless CSV file, read from STDIN.
 /// Each record must have two fields - a placeholder-name and a value.
 /// The delimiter is an ASCII comma.
@@ -31,9 +47,17 @@ use terminal_size::Width;
 /// Fields may be quoted with ASCII double quote characters.
 /// If you need to use an ASCII double quote you can escape it by doubling it.
 /// If a record starts with a hash character, this line is ignored.
+///
+/// Placeholder delimiters, the data file delimiter, a default output directory and template
+/// search directories can be overridden by an optional `fillout.toml`/`fillout.yaml` file,
+/// discovered by walking up from the current directory.
 #[derive(Debug, StructOpt)]
 struct Opt {
-    /// A template-file - must have the .tmpl file extension.
+    /// A template-file, a directory to recursively search for template-files, or a glob
+    /// pattern such as templates/**/*.tmpl - template-files must have the .tmpl file
+    /// extension (configurable, matched case-insensitively). A template-file whose name
+    /// starts with an underscore, e.g. _signature.tmpl, is an include-only partial and is
+    /// skipped by directory and glob search - it is still used when named by an include.
     #[structopt(parse(from_os_str), name = "TEMPLATE-FILE")]
     template_files: Vec<PathBuf>,
 
@@ -43,6 +67,82 @@ struct Opt {
     output_dir: Option<PathBuf>,
 }
 
+/// Reads `name` and every template it transitively includes into `arena`, keyed by include
+/// name, using `loader` to resolve a name to its corpus. `stack` tracks the chain of includes
+/// currently being loaded so a cycle can be reported with the full chain that caused it.
+fn discover_includes(
+    name: &str,
+    loader: &dyn Fn(&str) -> Result<String>,
+    open: &str,
+    close: &str,
+    arena: &mut HashMap<String, String>,
+    stack: &mut Vec<String>,
+) -> Result<()> {
+    if stack.iter().any(|included| included == name) {
+        let mut chain = stack.clone();
+        chain.push(name.to_string());
+        bail!("Include cycle detected: {}", chain.join(" -> "));
+    }
+    if arena.contains_key(name) {
+        return Ok(());
+    }
+
+    let corpus = loader(name).with_context(|| format!("Failed to load include {:?}", name))?;
+    let included_names: Vec<String> = match template::parse(&corpus, open, close) {
+        Ok(tokens) => tokens
+            .into_iter()
+            .filter_map(|token| token.as_include().map(str::to_string))
+            .collect(),
+        Err((first, rest)) => {
+            let mut errors = vec![first];
+            errors.extend(rest);
+            diagnostics::report(Path::new(name), &corpus, &errors);
+            bail!("Failed to parse include {:?}", name);
+        }
+    };
+    arena.insert(name.to_string(), corpus);
+
+    stack.push(name.to_string());
+    for included_name in included_names {
+        discover_includes(&included_name, loader, open, close, arena, stack)?;
+    }
+    stack.pop();
+
+    Ok(())
+}
+
+/// Replaces every `Token::Include` in `tokens` with the (recursively resolved) tokens of the
+/// template it names, looking up include corpora already loaded into `arena`.
+fn resolve_includes<'a>(
+    tokens: Vec<template::Token<'a>>,
+    arena: &'a HashMap<String, String>,
+    open: &str,
+    close: &str,
+) -> Result<Vec<template::Token<'a>>> {
+    let mut result = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        match token.as_include() {
+            Some(name) => {
+                let corpus = arena
+                    .get(name)
+                    .expect("include corpus should have been discovered up-front");
+                let included = match template::parse(corpus, open, close) {
+                    Ok(tokens) => tokens,
+                    Err((first, rest)) => {
+                        let mut errors = vec![first];
+                        errors.extend(rest);
+                        diagnostics::report(Path::new(name), corpus, &errors);
+                        bail!("Failed to parse include {:?}", name);
+                    }
+                };
+                result.extend(resolve_includes(included, arena, open, close)?);
+            }
+            None => result.push(token),
+        }
+    }
+    Ok(result)
+}
+
 fn main() -> Result<()> {
     use std::collections::HashSet;
     use std::fs::read_to_string;
@@ -59,29 +159,52 @@ fn main() -> Result<()> {
     };
 
     let opt = Opt::from_clap(&Opt::clap().set_term_width(width).get_matches());
+    let config = config::Config::load().context("Failed to load config")?;
 
-    let mut templates = vec![];
-    let mut outputs = vec![];
-    for template in &opt.template_files {
-        if template.extension() == Some(OsStr::new("tmpl")) {
-            let template = template
-                .canonicalize()
-                .with_context(|| format!("Failed to resolve template-file: {:?}", template))?;
-            let output_dir = opt
-                .output_dir
-                .as_ref()
-                .map(PathBuf::clone)
-                .unwrap_or_else(|| template.parent().unwrap().to_path_buf());
-            let output_file = template.file_stem().unwrap();
-            outputs.push(output_dir.join(output_file));
-            templates.push(template);
-        } else {
-            eprintln!(
-                "Error: template-file must have .tmpl file extension: {:?}",
-                template
-            );
+    let loader = |name: &str| -> Result<String> {
+        for dir in &config.template_dirs {
+            let path = dir.join(format!("{}.{}", name, config.template_extension));
+            if path.is_file() {
+                return read_to_string(&path)
+                    .with_context(|| format!("Failed to read include-file {:?}", path));
+            }
+        }
+        Err(anyhow!(
+            "Could not find include {:?} in any template-dir",
+            name
+        ))
+    };
+
+    let output_dir = opt
+        .output_dir
+        .as_ref()
+        .map(PathBuf::clone)
+        .or_else(|| config.output_dir.clone());
+
+    let inputs = match discover::expand(&opt.template_files, &config.template_extension) {
+        Ok(inputs) => inputs,
+        Err(err) => {
+            eprintln!("Error: {:#}", err);
             exit(exitcode::USAGE);
         }
+    };
+
+    let mut templates = vec![];
+    let mut outputs = vec![];
+    for discover::Input {
+        template_file,
+        relative_path,
+    } in inputs
+    {
+        let output_file = match &output_dir {
+            Some(dir) => dir.join(relative_path.with_extension("")),
+            None => template_file
+                .parent()
+                .unwrap()
+                .join(template_file.file_stem().unwrap()),
+        };
+        outputs.push(output_file);
+        templates.push(template_file);
     }
 
     let templates: Vec<_> = templates
@@ -93,13 +216,51 @@ fn main() -> Result<()> {
         })
         .collect::<Result<_, _>>()?;
 
+    let mut had_parse_errors = false;
+    let mut parsed_templates = vec![];
+    for (path, corpus) in &templates {
+        match template::parse(corpus, &config.delimiter_open, &config.delimiter_close) {
+            Ok(tokens) => parsed_templates.push((path, tokens)),
+            Err((first, rest)) => {
+                had_parse_errors = true;
+                let mut errors = vec![first];
+                errors.extend(rest);
+                diagnostics::report(path, corpus, &errors);
+            }
+        }
+    }
+    if had_parse_errors {
+        exit(exitcode::DATAERR);
+    }
+    let templates = parsed_templates;
+
+    let mut include_arena: HashMap<String, String> = HashMap::new();
+    for (path, tokens) in &templates {
+        for name in tokens.iter().filter_map(|token| token.as_include()) {
+            let mut stack = vec![];
+            discover_includes(
+                name,
+                &loader,
+                &config.delimiter_open,
+                &config.delimiter_close,
+                &mut include_arena,
+                &mut stack,
+            )
+            .with_context(|| format!("Failed to resolve includes in template-file {:?}", path))?;
+        }
+    }
+
     let templates: Vec<_> = templates
-        .iter()
-        .map(|(path, corpus)| {
-            template::parse(&corpus)
-                .map_err(|(first, _)| first)
-                .with_context(|| format!("Failed to parse template-file {:?}", path))
-                .map(|tokens| (path, tokens))
+        .into_iter()
+        .map(|(path, tokens)| {
+            resolve_includes(
+                tokens,
+                &include_arena,
+                &config.delimiter_open,
+                &config.delimiter_close,
+            )
+            .with_context(|| format!("Failed to resolve includes in template-file {:?}", path))
+            .map(|tokens| (path, tokens))
         })
         .collect::<Result<_, _>>()?;
 
@@ -115,7 +276,8 @@ fn main() -> Result<()> {
         .read_to_end(&mut input)
         .context("Failed to read data file from stdin")?;
     let input = input;
-    let input_vars = data::parse(&input).context("Failed to validate data file")?;
+    let input_vars =
+        data::parse(&input, config.csv_delimiter).context("Failed to validate data file")?;
 
     let extra = input_vars
         .keys()
@@ -145,6 +307,10 @@ fn main() -> Result<()> {
     let outputs: Vec<_> = outputs
         .into_iter()
         .map(|path| {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create output directory {:?}", parent))?;
+            }
             OpenOptions::new()
                 .create(true)
                 .write(true)
@@ -156,11 +322,12 @@ fn main() -> Result<()> {
         .collect::<Result<_, _>>()?;
 
     for ((_, template), (path, mut output)) in templates.into_iter().zip(outputs) {
+        let default_escape = Escape::for_extension(path.extension().and_then(|ext| ext.to_str()));
         output
             .set_len(0)
             .with_context(|| format!("Failed to truncate output file {:?}", path))?;
         for token in template {
-            write!(output, "{}", token.eval(&input_vars))
+            write!(output, "{}", token.eval(&input_vars, default_escape))
                 .with_context(|| format!("Failed writing to output file {:?}", path))?;
         }
     }
@@ -1,44 +1,78 @@
 pub mod token {
+    use crate::escape::Escape;
+    use std::borrow::Cow;
     use std::collections::HashMap;
     use std::fmt;
 
     #[derive(Debug, Eq, PartialEq)]
     pub enum Token<'a> {
         Lit(&'a str),
-        Var(&'a str),
+        /// A `{{ name }}` placeholder. The optional `Escape` is a `{{ name | mode }}`
+        /// override of the output-file's default escaping mode.
+        Var(&'a str, Option<Escape>),
+        /// A `{{> name }}` placeholder, naming another template to splice in here.
+        /// Resolved into the included template's own tokens before evaluation.
+        Include(&'a str),
     }
 
     impl<'a> Token<'a> {
         pub fn as_var(&self) -> Option<&'a str> {
-            if let Token::Var(s) = self {
+            if let Token::Var(s, _) = self {
                 Some(s)
             } else {
                 None
             }
         }
 
-        pub fn eval(&self, ctx: &'a HashMap<String, String>) -> &'a str {
+        pub fn as_include(&self) -> Option<&'a str> {
+            if let Token::Include(s) = self {
+                Some(s)
+            } else {
+                None
+            }
+        }
+
+        /// Substitutes the token's value, escaping it with its own `{{ name | mode }}`
+        /// override if it has one, or `default_escape` otherwise.
+        pub fn eval(
+            &self,
+            ctx: &'a HashMap<String, String>,
+            default_escape: Escape,
+        ) -> Cow<'a, str> {
             match self {
-                Token::Lit(s) => s,
-                Token::Var(n) => ctx[*n].as_str(),
+                Token::Lit(s) => Cow::Borrowed(s),
+                Token::Var(n, escape) => {
+                    let value = ctx[*n].as_str();
+                    match escape.unwrap_or(default_escape) {
+                        Escape::None => Cow::Borrowed(value),
+                        escape => Cow::Owned(escape.apply(value)),
+                    }
+                }
+                Token::Include(name) => {
+                    unreachable!("include {:?} should have been resolved before eval", name)
+                }
             }
         }
     }
 
     #[derive(Debug, Eq, PartialEq)]
     pub enum Error {
-        ExpectedDoubleRightBraces(usize),
-        UnexpectedEndOfFile,
+        ExpectedClose(usize, String),
+        UnexpectedEndOfFile(usize),
+        UnknownEscape(usize, String),
     }
 
     impl fmt::Display for Error {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             match self {
-                Error::ExpectedDoubleRightBraces(pos) => {
-                    write!(f, "expected \"}}\" at position {}", pos)
+                Error::ExpectedClose(pos, close) => {
+                    write!(f, "expected {:?} at position {}", close, pos)
+                }
+                Error::UnexpectedEndOfFile(pos) => {
+                    write!(f, "unexpected end of file at position {}", pos)
                 }
-                Error::UnexpectedEndOfFile => {
-                    write!(f, "unexpected end of file")
+                Error::UnknownEscape(pos, name) => {
+                    write!(f, "unknown escape mode {:?} at position {}", name, pos)
                 }
             }
         }
@@ -46,6 +80,27 @@ pub mod token {
 
     impl std::error::Error for Error {}
 
+    impl Error {
+        /// The byte offset into the corpus this error applies to, for diagnostics.
+        pub fn offset(&self) -> usize {
+            match self {
+                Error::ExpectedClose(pos, _) => *pos,
+                Error::UnknownEscape(pos, _) => *pos,
+                Error::UnexpectedEndOfFile(pos) => *pos,
+            }
+        }
+
+        /// A short, position-free description of the error, suitable for pairing with a
+        /// caret pointing at its `offset` in the source.
+        pub fn short_message(&self) -> String {
+            match self {
+                Error::ExpectedClose(_, close) => format!("expected {:?}", close),
+                Error::UnexpectedEndOfFile(_) => "unexpected end of file".to_string(),
+                Error::UnknownEscape(_, name) => format!("unknown escape mode {:?}", name),
+            }
+        }
+    }
+
     enum State {
         Lit,
         Var,
@@ -56,12 +111,19 @@ pub mod token {
         corpus: &'a str,
         offset: usize,
         start: usize,
+        open: String,
+        close: String,
     }
 
     impl<'a> Tokenizer<'a> {
-        pub fn new(corpus: &'a str) -> Self {
-            let (state, offset) = if corpus.starts_with("{{") {
-                (State::Var, 2)
+        pub fn with_delimiters(
+            corpus: &'a str,
+            open: impl Into<String>,
+            close: impl Into<String>,
+        ) -> Self {
+            let open = open.into();
+            let (state, offset) = if corpus.starts_with(open.as_str()) {
+                (State::Var, open.len())
             } else {
                 (State::Lit, 0)
             };
@@ -70,6 +132,8 @@ pub mod token {
                 corpus,
                 offset,
                 start: 0,
+                open,
+                close: close.into(),
             }
         }
     }
@@ -82,7 +146,7 @@ pub mod token {
             } else {
                 let (token, state, delta, offset) = match self.state {
                     State::Lit => {
-                        if let Some(i) = self.corpus[self.offset..].find("{{") {
+                        if let Some(i) = self.corpus[self.offset..].find(self.open.as_str()) {
                             (
                                 Ok(Token::Lit(&self.corpus[..self.offset + i])),
                                 State::Var,
@@ -99,29 +163,52 @@ pub mod token {
                         }
                     }
                     State::Var => {
-                        if let Some(i) = self.corpus[2..].find(|c| c == '{' || c == '}') {
-                            if self.corpus[2 + i..].starts_with("}}") {
-                                let state = if self.corpus[4 + i..].starts_with("{{") {
-                                    State::Var
-                                } else {
-                                    State::Lit
-                                };
-                                (
-                                    Ok(Token::Var(&self.corpus[2..2 + i].trim())),
-                                    state,
-                                    4 + i,
-                                    0,
-                                )
-                            } else {
+                        let body = self.open.len();
+                        if let Some(i) = self.corpus[body..].find(self.close.as_str()) {
+                            if let Some(j) = self.corpus[body..body + i].find(self.open.as_str()) {
                                 (
-                                    Err(Error::ExpectedDoubleRightBraces(self.start + 2 + i)),
+                                    Err(Error::ExpectedClose(
+                                        self.start + body + j,
+                                        self.close.clone(),
+                                    )),
                                     State::Lit,
                                     0,
-                                    2 + i,
+                                    body + j,
                                 )
+                            } else {
+                                let end = body + i + self.close.len();
+                                let state = if self.corpus[end..].starts_with(self.open.as_str()) {
+                                    State::Var
+                                } else {
+                                    State::Lit
+                                };
+                                let raw = &self.corpus[body..body + i];
+                                let text = raw.trim();
+                                let token = if let Some(name) = text.strip_prefix('>') {
+                                    Ok(Token::Include(name.trim()))
+                                } else if let Some(p) = raw.find('|') {
+                                    let escape_name = raw[p + 1..].trim();
+                                    match Escape::from_name(escape_name) {
+                                        Some(escape) => {
+                                            Ok(Token::Var(raw[..p].trim(), Some(escape)))
+                                        }
+                                        None => Err(Error::UnknownEscape(
+                                            self.start + body + p,
+                                            escape_name.to_string(),
+                                        )),
+                                    }
+                                } else {
+                                    Ok(Token::Var(text, None))
+                                };
+                                (token, state, end, 0)
                             }
                         } else {
-                            (Err(Error::UnexpectedEndOfFile), State::Lit, 0, 2)
+                            (
+                                Err(Error::UnexpectedEndOfFile(self.start + body)),
+                                State::Lit,
+                                0,
+                                body,
+                            )
                         }
                     }
                 };
@@ -140,100 +227,158 @@ pub mod token {
 
         #[test]
         fn empty() {
-            let mut t = Tokenizer::new("");
+            let mut t = Tokenizer::with_delimiters("", "{{", "}}");
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn lit() {
-            let mut t = Tokenizer::new("lorem");
+            let mut t = Tokenizer::with_delimiters("lorem", "{{", "}}");
             assert_eq!(t.next(), Some(Ok(Token::Lit("lorem"))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn var() {
-            let mut t = Tokenizer::new("{{lorem}}");
-            assert_eq!(t.next(), Some(Ok(Token::Var("lorem"))));
+            let mut t = Tokenizer::with_delimiters("{{lorem}}", "{{", "}}");
+            assert_eq!(t.next(), Some(Ok(Token::Var("lorem", None))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn var_trim() {
-            let mut t = Tokenizer::new("{{ lorem }}");
-            assert_eq!(t.next(), Some(Ok(Token::Var("lorem"))));
+            let mut t = Tokenizer::with_delimiters("{{ lorem }}", "{{", "}}");
+            assert_eq!(t.next(), Some(Ok(Token::Var("lorem", None))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn var_var() {
-            let mut t = Tokenizer::new("{{lorem}}{{ipsum}}");
-            assert_eq!(t.next(), Some(Ok(Token::Var("lorem"))));
-            assert_eq!(t.next(), Some(Ok(Token::Var("ipsum"))));
+            let mut t = Tokenizer::with_delimiters("{{lorem}}{{ipsum}}", "{{", "}}");
+            assert_eq!(t.next(), Some(Ok(Token::Var("lorem", None))));
+            assert_eq!(t.next(), Some(Ok(Token::Var("ipsum", None))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn lit_var() {
-            let mut t = Tokenizer::new("lorem{{ipsum}}");
+            let mut t = Tokenizer::with_delimiters("lorem{{ipsum}}", "{{", "}}");
             assert_eq!(t.next(), Some(Ok(Token::Lit("lorem"))));
-            assert_eq!(t.next(), Some(Ok(Token::Var("ipsum"))));
+            assert_eq!(t.next(), Some(Ok(Token::Var("ipsum", None))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn var_lit() {
-            let mut t = Tokenizer::new("{{lorem}}ipsum");
-            assert_eq!(t.next(), Some(Ok(Token::Var("lorem"))));
+            let mut t = Tokenizer::with_delimiters("{{lorem}}ipsum", "{{", "}}");
+            assert_eq!(t.next(), Some(Ok(Token::Var("lorem", None))));
             assert_eq!(t.next(), Some(Ok(Token::Lit("ipsum"))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn edrb_var() {
-            let mut t = Tokenizer::new("{{lorem{{ipsum}}");
-            assert_eq!(t.next(), Some(Err(Error::ExpectedDoubleRightBraces(7))));
+            let mut t = Tokenizer::with_delimiters("{{lorem{{ipsum}}", "{{", "}}");
+            assert_eq!(
+                t.next(),
+                Some(Err(Error::ExpectedClose(7, "}}".to_string())))
+            );
             assert_eq!(t.next(), Some(Ok(Token::Lit("{{lorem"))));
-            assert_eq!(t.next(), Some(Ok(Token::Var("ipsum"))));
+            assert_eq!(t.next(), Some(Ok(Token::Var("ipsum", None))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn edrb_var_edrb_var() {
-            let mut t = Tokenizer::new("{{lorem{{ipsum}}{{dolor{{sit}}");
-            assert_eq!(t.next(), Some(Err(Error::ExpectedDoubleRightBraces(7))));
+            let mut t = Tokenizer::with_delimiters("{{lorem{{ipsum}}{{dolor{{sit}}", "{{", "}}");
+            assert_eq!(
+                t.next(),
+                Some(Err(Error::ExpectedClose(7, "}}".to_string())))
+            );
             assert_eq!(t.next(), Some(Ok(Token::Lit("{{lorem"))));
-            assert_eq!(t.next(), Some(Ok(Token::Var("ipsum"))));
-            assert_eq!(t.next(), Some(Err(Error::ExpectedDoubleRightBraces(23))));
+            assert_eq!(t.next(), Some(Ok(Token::Var("ipsum", None))));
+            assert_eq!(
+                t.next(),
+                Some(Err(Error::ExpectedClose(23, "}}".to_string())))
+            );
             assert_eq!(t.next(), Some(Ok(Token::Lit("{{dolor"))));
-            assert_eq!(t.next(), Some(Ok(Token::Var("sit"))));
+            assert_eq!(t.next(), Some(Ok(Token::Var("sit", None))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
-        fn edrb_lit() {
-            let mut t = Tokenizer::new("{{lorem}ipsum");
-            assert_eq!(t.next(), Some(Err(Error::ExpectedDoubleRightBraces(7))));
+        fn unclosed_lit() {
+            let mut t = Tokenizer::with_delimiters("{{lorem}ipsum", "{{", "}}");
+            assert_eq!(t.next(), Some(Err(Error::UnexpectedEndOfFile(2))));
             assert_eq!(t.next(), Some(Ok(Token::Lit("{{lorem}ipsum"))));
             assert_eq!(t.next(), None);
         }
 
         #[test]
         fn ueof_lit() {
-            let mut t = Tokenizer::new("{{lorem");
-            assert_eq!(t.next(), Some(Err(Error::UnexpectedEndOfFile)));
+            let mut t = Tokenizer::with_delimiters("{{lorem", "{{", "}}");
+            assert_eq!(t.next(), Some(Err(Error::UnexpectedEndOfFile(2))));
             assert_eq!(t.next(), Some(Ok(Token::Lit("{{lorem"))));
             assert_eq!(t.next(), None);
         }
+
+        #[test]
+        fn ueof_ueof() {
+            let mut t = Tokenizer::with_delimiters("{{lorem\n{{ipsum", "{{", "}}");
+            assert_eq!(t.next(), Some(Err(Error::UnexpectedEndOfFile(2))));
+            assert_eq!(t.next(), Some(Ok(Token::Lit("{{lorem\n"))));
+            assert_eq!(t.next(), Some(Err(Error::UnexpectedEndOfFile(10))));
+            assert_eq!(t.next(), Some(Ok(Token::Lit("{{ipsum"))));
+            assert_eq!(t.next(), None);
+        }
+
+        #[test]
+        fn custom_delimiters() {
+            let mut t = Tokenizer::with_delimiters("<% lorem %>ipsum", "<%", "%>");
+            assert_eq!(t.next(), Some(Ok(Token::Var("lorem", None))));
+            assert_eq!(t.next(), Some(Ok(Token::Lit("ipsum"))));
+            assert_eq!(t.next(), None);
+        }
+
+        #[test]
+        fn include() {
+            let mut t = Tokenizer::with_delimiters("{{> header }}", "{{", "}}");
+            assert_eq!(t.next(), Some(Ok(Token::Include("header"))));
+            assert_eq!(t.next(), None);
+        }
+
+        #[test]
+        fn var_escape_override() {
+            let mut t = Tokenizer::with_delimiters("{{ name | raw }}", "{{", "}}");
+            assert_eq!(
+                t.next(),
+                Some(Ok(Token::Var("name", Some(crate::escape::Escape::None))))
+            );
+            assert_eq!(t.next(), None);
+        }
+
+        #[test]
+        fn var_unknown_escape() {
+            let mut t = Tokenizer::with_delimiters("{{ name | bogus }}", "{{", "}}");
+            assert_eq!(
+                t.next(),
+                Some(Err(Error::UnknownEscape(8, "bogus".to_string())))
+            );
+            assert_eq!(t.next(), None);
+        }
     }
 }
 
 pub use token::Error;
 pub use token::Token;
 
-pub fn parse(corpus: &str) -> Result<Vec<Token<'_>>, (Error, Vec<Error>)> {
+pub fn parse<'a>(
+    corpus: &'a str,
+    open: &str,
+    close: &str,
+) -> Result<Vec<Token<'a>>, (Error, Vec<Error>)> {
     let mut result = Ok(vec![]);
-    for token in token::Tokenizer::new(corpus) {
+    for token in token::Tokenizer::with_delimiters(corpus, open, close) {
         result = match (result, token) {
             (Ok(mut result), Ok(token)) => {
                 result.push(token);
@@ -256,49 +401,72 @@ mod tests {
 
     #[test]
     fn empty() {
-        let res = parse("");
+        let res = parse("", "{{", "}}");
 
         assert_eq!(res, Ok(vec![]));
     }
 
     #[test]
     fn lit() {
-        let res = parse("lorem");
+        let res = parse("lorem", "{{", "}}");
 
         assert_eq!(res, Ok(vec![Token::Lit("lorem")]));
     }
 
     #[test]
     fn var() {
-        let res = parse("{{lorem}}");
+        let res = parse("{{lorem}}", "{{", "}}");
 
-        assert_eq!(res, Ok(vec![Token::Var("lorem")]));
+        assert_eq!(res, Ok(vec![Token::Var("lorem", None)]));
     }
 
     #[test]
     fn var_var() {
-        let res = parse("{{lorem}}{{ipsum}}");
+        let res = parse("{{lorem}}{{ipsum}}", "{{", "}}");
 
-        assert_eq!(res, Ok(vec![Token::Var("lorem"), Token::Var("ipsum")]));
+        assert_eq!(
+            res,
+            Ok(vec![Token::Var("lorem", None), Token::Var("ipsum", None)])
+        );
     }
 
     #[test]
     fn edrb_var() {
-        let res = parse("{{lorem{{ipsum}}");
+        let res = parse("{{lorem{{ipsum}}", "{{", "}}");
 
-        assert_eq!(res, Err((Error::ExpectedDoubleRightBraces(7), vec![])));
+        assert_eq!(
+            res,
+            Err((Error::ExpectedClose(7, "}}".to_string()), vec![]))
+        );
     }
 
     #[test]
     fn edrb_var_edrb_var() {
-        let res = parse("{{lorem{{ipsum}}{{dolor{{sit}}");
+        let res = parse("{{lorem{{ipsum}}{{dolor{{sit}}", "{{", "}}");
 
         assert_eq!(
             res,
             Err((
-                Error::ExpectedDoubleRightBraces(7),
-                vec![Error::ExpectedDoubleRightBraces(23)]
+                Error::ExpectedClose(7, "}}".to_string()),
+                vec![Error::ExpectedClose(23, "}}".to_string())]
             ))
         );
     }
+
+    #[test]
+    fn custom_delimiters() {
+        let res = parse("[[lorem]]", "[[", "]]");
+
+        assert_eq!(res, Ok(vec![Token::Var("lorem", None)]));
+    }
+
+    #[test]
+    fn include() {
+        let res = parse("{{> header }}{{body}}", "{{", "}}");
+
+        assert_eq!(
+            res,
+            Ok(vec![Token::Include("header"), Token::Var("body", None)])
+        );
+    }
 }